@@ -0,0 +1,126 @@
+//! A case-insensitive, order-preserving map from header name to header
+//! value, as found in an email message.
+//!
+//! Header names are compared and hashed ignoring case (per RFC 5322,
+//! `Subject` and `subject` name the same header) while the casing the
+//! message actually used is preserved for display, and headers keep the
+//! order they appeared in the file.
+
+use std::fmt;
+use std::ops::Index;
+
+/// Headers names are almost always short (`From`, `Subject`,
+/// `Content-Type`, ...), so store up to this many bytes inline and only
+/// spill to the heap for the rare oversized one.
+const INLINE_CAP: usize = 32;
+
+#[derive(Clone, Debug)]
+enum Repr {
+    Inline { buf: [u8; INLINE_CAP], len: u8 },
+    Heap(Vec<u8>),
+}
+
+/// A header name, e.g. `From` or `Content-Type`.
+///
+/// Compares and hashes case-insensitively while remembering the original
+/// casing, so a [`HeaderMap`] keyed by `HeaderName` can be looked up with
+/// any casing but still print headers the way the message wrote them.
+#[derive(Clone, Debug)]
+pub struct HeaderName(Repr);
+
+impl HeaderName {
+    pub fn new(name: &str) -> Self {
+        let bytes = name.as_bytes();
+
+        if bytes.len() <= INLINE_CAP {
+            let mut buf = [0u8; INLINE_CAP];
+            buf[..bytes.len()].copy_from_slice(bytes);
+
+            HeaderName(Repr::Inline {
+                buf,
+                len: bytes.len() as u8,
+            })
+        } else {
+            HeaderName(Repr::Heap(bytes.to_vec()))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        let bytes = match &self.0 {
+            Repr::Inline { buf, len } => &buf[..*len as usize],
+            Repr::Heap(v) => v.as_slice(),
+        };
+
+        // Constructed only from valid UTF-8 str slices in `new`.
+        std::str::from_utf8(bytes).unwrap_or("")
+    }
+}
+
+impl PartialEq for HeaderName {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str().eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl Eq for HeaderName {}
+
+impl std::hash::Hash for HeaderName {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for b in self.as_str().bytes() {
+            b.to_ascii_lowercase().hash(state);
+        }
+    }
+}
+
+impl fmt::Display for HeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A case-insensitive, order-preserving map from header name to header
+/// value.
+#[derive(Clone, Debug, Default)]
+pub struct HeaderMap {
+    entries: Vec<(HeaderName, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Insert a header, overwriting the value of an existing header with
+    /// the same name (case-insensitively) while keeping its original
+    /// position, or appending a new entry otherwise.
+    pub fn insert(&mut self, name: &str, value: String) {
+        let key = HeaderName::new(name);
+
+        if let Some(entry) = self.entries.iter_mut().find(|(n, _)| *n == key) {
+            entry.1 = value;
+        } else {
+            self.entries.push((key, value));
+        }
+    }
+
+    /// Look up a header's value by name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let key = HeaderName::new(name);
+
+        self.entries
+            .iter()
+            .find(|(n, _)| *n == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Index<&str> for HeaderMap {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no header named {}", name))
+    }
+}
@@ -0,0 +1,316 @@
+//! A small RFC 5322 mailbox parser.
+//!
+//! Splits a `From`-style header value into display name(s) and
+//! addr-spec(s), handling quoted display names with escaped characters,
+//! comments in parentheses, and group/multiple-address lists. The input
+//! is expected to already have gone through encoded-word decoding (see
+//! [`crate::parse_header_line`]), so this operates on plain Unicode text.
+
+/// A single mailbox: an optional display name and its address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mailbox {
+    pub display_name: Option<String>,
+    pub address: String,
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            chars: input.chars().peekable(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    /// Skip whitespace and `(...)` comments, which may contain `\`-escaped
+    /// characters and nest.
+    fn skip_cfws(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('(') => {
+                    self.chars.next();
+                    let mut depth = 1;
+
+                    while depth > 0 {
+                        match self.chars.next() {
+                            Some('\\') => {
+                                self.chars.next();
+                            }
+                            Some('(') => depth += 1,
+                            Some(')') => depth -= 1,
+                            Some(_) => (),
+                            None => break,
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Parse a `"..."` quoted-string, unescaping `\`-escaped characters.
+    /// The opening quote must already be the next character.
+    fn parse_quoted_string(&mut self) -> String {
+        let mut s = String::new();
+        self.chars.next(); // opening quote
+
+        loop {
+            match self.chars.next() {
+                Some('"') | None => break,
+                Some('\\') => {
+                    if let Some(c) = self.chars.next() {
+                        s.push(c);
+                    }
+                }
+                Some(c) => s.push(c),
+            }
+        }
+
+        s
+    }
+
+    /// Parse a bare word (an atom, or a run of non-special characters) up
+    /// to the next piece of syntax.
+    fn parse_word(&mut self) -> String {
+        let mut s = String::new();
+
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "()<>,:;\"".contains(c) {
+                break;
+            }
+
+            s.push(c);
+            self.chars.next();
+        }
+
+        s
+    }
+
+    /// Parse a display-name phrase: a run of quoted-strings and/or bare
+    /// words, stopping at `<`, `,`, `:`, or `;`.
+    fn parse_phrase(&mut self) -> Option<String> {
+        let mut words = Vec::new();
+
+        loop {
+            self.skip_cfws();
+
+            match self.peek() {
+                Some('"') => words.push(self.parse_quoted_string()),
+                Some(c) if !"<>,:;".contains(c) => {
+                    let w = self.parse_word();
+                    if w.is_empty() {
+                        break;
+                    }
+                    words.push(w);
+                }
+                _ => break,
+            }
+        }
+
+        if words.is_empty() {
+            None
+        } else {
+            Some(words.join(" "))
+        }
+    }
+
+    /// Parse an addr-spec: `local-part@domain`, where the local part may
+    /// be a quoted-string.
+    fn parse_addr_spec(&mut self) -> String {
+        let mut s = String::new();
+
+        self.skip_cfws();
+
+        if self.peek() == Some('"') {
+            s.push('"');
+            s.push_str(&self.parse_quoted_string());
+            s.push('"');
+        } else {
+            s.push_str(&self.parse_word());
+        }
+
+        self.skip_cfws();
+
+        if self.peek() == Some('@') {
+            self.chars.next();
+            s.push('@');
+            self.skip_cfws();
+            s.push_str(&self.parse_word());
+        }
+
+        s
+    }
+
+    /// Parse one `mailbox`: either `display name <addr-spec>` or a bare
+    /// `addr-spec`.
+    fn parse_mailbox(&mut self) -> Option<Mailbox> {
+        self.skip_cfws();
+
+        self.peek()?;
+
+        let phrase = self.parse_phrase();
+        self.skip_cfws();
+
+        if self.peek() == Some('<') {
+            self.chars.next();
+            let address = self.parse_addr_spec();
+            self.skip_cfws();
+
+            if self.peek() == Some('>') {
+                self.chars.next();
+            }
+
+            Some(Mailbox {
+                display_name: phrase.filter(|s| !s.is_empty()),
+                address,
+            })
+        } else {
+            // No angle brackets: what we parsed as a "phrase" is really
+            // the addr-spec's local-part (e.g. a bare `user@host`).
+            let local_part = phrase.unwrap_or_default();
+            self.skip_cfws();
+
+            let address = if self.peek() == Some('@') {
+                self.chars.next();
+                self.skip_cfws();
+                format!("{}@{}", local_part, self.parse_word())
+            } else {
+                local_part
+            };
+
+            Some(Mailbox {
+                display_name: None,
+                address,
+            })
+        }
+    }
+
+    /// Parse a comma-separated `mailbox-list`, also accepting an RFC 5322
+    /// `group` (`name: member, member;`), whose members are flattened
+    /// into the result.
+    fn parse_mailbox_list(&mut self) -> Vec<Mailbox> {
+        let mut mailboxes = Vec::new();
+
+        loop {
+            self.skip_cfws();
+
+            if self.peek().is_none() {
+                break;
+            }
+
+            let start_phrase = self.parse_phrase();
+            self.skip_cfws();
+
+            if self.peek() == Some(':') {
+                // It was a group name, not a display name: recurse into
+                // the member list up to the closing `;`.
+                self.chars.next();
+                mailboxes.extend(self.parse_mailbox_list_until(Some(';')));
+                self.skip_cfws();
+
+                if self.peek() == Some(';') {
+                    self.chars.next();
+                }
+            } else if self.peek() == Some('<') {
+                self.chars.next();
+                let address = self.parse_addr_spec();
+                self.skip_cfws();
+
+                if self.peek() == Some('>') {
+                    self.chars.next();
+                }
+
+                mailboxes.push(Mailbox {
+                    display_name: start_phrase.filter(|s| !s.is_empty()),
+                    address,
+                });
+            } else {
+                let local_part = start_phrase.unwrap_or_default();
+                self.skip_cfws();
+
+                let address = if self.peek() == Some('@') {
+                    self.chars.next();
+                    self.skip_cfws();
+                    format!("{}@{}", local_part, self.parse_word())
+                } else {
+                    local_part
+                };
+
+                if !address.is_empty() {
+                    mailboxes.push(Mailbox {
+                        display_name: None,
+                        address,
+                    });
+                }
+            }
+
+            self.skip_cfws();
+
+            match self.peek() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+
+        mailboxes
+    }
+
+    fn parse_mailbox_list_until(&mut self, stop: Option<char>) -> Vec<Mailbox> {
+        // Group member lists are a plain mailbox-list terminated by `;`
+        // rather than end-of-input; reuse the same grammar and just stop
+        // the outer loop when we see the terminator.
+        let mut mailboxes = Vec::new();
+
+        loop {
+            self.skip_cfws();
+
+            if self.peek().is_none() || self.peek() == stop {
+                break;
+            }
+
+            if let Some(mb) = self.parse_mailbox() {
+                if !mb.address.is_empty() || mb.display_name.is_some() {
+                    mailboxes.push(mb);
+                }
+            } else {
+                break;
+            }
+
+            self.skip_cfws();
+
+            match self.peek() {
+                Some(',') => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+
+        mailboxes
+    }
+}
+
+/// Parse a `From`-style header value into its mailbox(es).
+///
+/// Accepts a single mailbox, a comma-separated mailbox list, or a group
+/// (`Team: a@x, b@x;`), whose members are flattened into the result.
+pub fn parse_mailbox_list(input: &str) -> Vec<Mailbox> {
+    Parser::new(input).parse_mailbox_list()
+}
+
+/// Parse a `From`-style header value and return its first mailbox, if
+/// any.
+pub fn parse_first_mailbox(input: &str) -> Option<Mailbox> {
+    parse_mailbox_list(input).into_iter().next()
+}
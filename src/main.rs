@@ -1,78 +1,180 @@
+mod headers;
+mod mailbox;
+mod mbox;
+mod query;
+
 use anyhow::{Context, Result, anyhow};
 use base64::prelude::*;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use colored::*;
 use encoding_rs::Encoding;
+use headers::HeaderMap;
+use mbox::MboxType;
 use quoted_printable::ParseMode;
-use regex::Captures;
 use regex_macro::regex;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Cursor, Write};
 use std::path::{Path, PathBuf};
 
-fn decode_charset_crate(charset: &str, encoded_text: &Vec<u8>) -> Result<String> {
-    let encoder = match Encoding::for_label(charset.to_ascii_lowercase().as_bytes()) {
-        Some(encoder) => encoder,
-        None => encoding_rs::WINDOWS_1252,
+/// Controls how `=?charset?...?=` encoded-words are decoded.
+#[derive(Clone, Copy, Debug)]
+struct DecodeOptions {
+    /// Charset to assume when a label isn't recognized by `encoding_rs`.
+    fallback_charset: &'static Encoding,
+    /// If true, a malformed byte sequence or unrecognized charset label
+    /// causes the encoded-word to be rejected (so the caller falls back
+    /// to the raw text) instead of being lossily replaced with U+FFFD.
+    strict: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            fallback_charset: encoding_rs::WINDOWS_1252,
+            strict: false,
+        }
+    }
+}
+
+fn decode_charset_crate(
+    charset: &str,
+    encoded_text: &Vec<u8>,
+    opts: &DecodeOptions,
+) -> Result<String> {
+    let (encoder, label_recognized) = match Encoding::for_label(charset.to_ascii_lowercase().as_bytes()) {
+        Some(encoder) => (encoder, true),
+        None => (opts.fallback_charset, false),
     };
 
-    let (s, _encoding_used, _malformed) = encoder.decode(&encoded_text);
+    if opts.strict && !label_recognized {
+        return Err(anyhow!("unrecognized charset label, {}", charset));
+    }
 
-    let out = s.replace("_", " ");
+    let (s, encoding_used, malformed) = encoder.decode(&encoded_text);
 
-    return Ok(out);
+    if opts.strict && malformed {
+        return Err(anyhow!(
+            "malformed byte sequence decoding as {}",
+            encoding_used.name()
+        ));
+    }
+
+    return Ok(s.into_owned());
 }
 
 fn decode_base64<'a>(data: &'a str) -> Result<Vec<u8>> {
     BASE64_STANDARD.decode(data).context("base64 error")
 }
 
-fn parse_encoding<'a>(charset: &str, encoding: &str, data: &'a str) -> Result<String> {
+fn parse_encoding<'a>(
+    charset: &str,
+    encoding: &str,
+    data: &'a str,
+    opts: &DecodeOptions,
+) -> Result<String> {
     let decoded = match encoding.to_ascii_uppercase().as_str() {
-        "Q" => quoted_printable::decode(data, ParseMode::Robust).context("Decoding failed"),
+        // Per RFC 2047, "_" represents a space, but only inside the Q
+        // encoding of an encoded-word; an escaped underscore is written
+        // as "=5F". Substitute before quoted-printable unescaping, since
+        // the quoted_printable crate has no notion of this convention.
+        "Q" => quoted_printable::decode(data.replace('_', " "), ParseMode::Robust)
+            .context("Decoding failed"),
         "B" => decode_base64(data),
         v @ _ => Err(anyhow!("Unknown encoding type, {}", v)),
     };
 
     match decoded {
-        Ok(v) => decode_charset_crate(charset, &v),
+        Ok(v) => decode_charset_crate(charset, &v, opts),
         Err(e) => Err(e),
     }
 }
 
-fn parse_header_line(header: &str) -> Result<String> {
+/// Decode all RFC 2047 encoded-words in a header value.
+///
+/// Per RFC 2047, linear white space between two adjacent encoded-words is
+/// not part of the displayed text and must be dropped, while whitespace
+/// between an encoded-word and ordinary text is preserved. This scans
+/// left-to-right, tracking whether the previous token was an
+/// encoded-word, and only drops the whitespace run in that case.
+fn parse_header_line(header: &str, opts: &DecodeOptions) -> Result<String> {
     let re = regex!(r"=\?([^?]+)\?([^?]+)\?(.*?)\?=");
 
-    let output = re.replace_all(header.trim_start(), |caps: &Captures| {
+    let header = header.trim_start();
+    let mut output = String::new();
+    let mut last_end = 0;
+    let mut prev_was_encoded_word = false;
+
+    for caps in re.captures_iter(header) {
+        let whole = caps.get(0).unwrap();
+        let between = &header[last_end..whole.start()];
+
+        if !(prev_was_encoded_word && !between.is_empty() && between.chars().all(char::is_whitespace))
+        {
+            output.push_str(between);
+        }
+
         let (Some(charset), Some(encoding), Some(encoded_text)) = (
             caps.get(1).map(|m| m.as_str()),
             caps.get(2).map(|m| m.as_str()),
             caps.get(3).map(|m| m.as_str()),
         ) else {
-            // If any part is missing, return the original match unmodified
-            return caps.get(0).map_or("", |m| m.as_str()).to_string();
+            // If any part is missing, emit the original match unmodified
+            output.push_str(whole.as_str());
+            last_end = whole.end();
+            prev_was_encoded_word = true;
+            continue;
         };
 
-        match parse_encoding(charset, encoding, encoded_text) {
-            Ok(s) => s,
+        match parse_encoding(charset, encoding, encoded_text, opts) {
+            Ok(s) => output.push_str(&s),
             Err(e) => {
                 eprintln!("Encoding error: {}", e);
-                caps.get(0).map_or("", |m| m.as_str()).to_string()
+                output.push_str(whole.as_str());
             }
         }
-    });
 
-    return Ok(output.into_owned());
-}
+        last_end = whole.end();
+        prev_was_encoded_word = true;
+    }
 
-fn read_headers(path: &Path, wanted: &HashSet<&str>) -> Result<HashMap<String, String>> {
-    let mut map = HashMap::new();
+    output.push_str(&header[last_end..]);
 
+    return Ok(output);
+}
+
+fn read_headers(path: &Path, wanted: &HashSet<String>, opts: &DecodeOptions) -> Result<HeaderMap> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
 
+    read_headers_from_reader(reader, wanted, opts)
+}
+
+/// Parse the headers out of a single message, already split out of its
+/// mbox file, in memory.
+fn read_headers_from_bytes(
+    data: &[u8],
+    wanted: &HashSet<String>,
+    opts: &DecodeOptions,
+) -> Result<HeaderMap> {
+    read_headers_from_reader(BufReader::new(Cursor::new(data)), wanted, opts)
+}
+
+/// `wanted` holds lowercase header names; membership is checked
+/// case-insensitively so callers don't need to know the casing a message
+/// actually used.
+fn is_wanted(wanted: &HashSet<String>, header_name: &str) -> bool {
+    wanted.contains(header_name.to_ascii_lowercase().as_str())
+}
+
+fn read_headers_from_reader<R: BufRead>(
+    reader: R,
+    wanted: &HashSet<String>,
+    opts: &DecodeOptions,
+) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+
     let mut header_name = String::new();
     let mut header_value = String::new();
 
@@ -89,20 +191,20 @@ fn read_headers(path: &Path, wanted: &HashSet<&str>) -> Result<HashMap<String, S
         }
 
         if let Some((header, rest)) = line.split_once(":") {
-            if !header_value.is_empty() && wanted.contains(header_name.as_str()) {
-                if let Ok(s) = parse_header_line(&header_value) {
-                    map.insert(header_name, s);
+            if !header_value.is_empty() && is_wanted(wanted, &header_name) {
+                if let Ok(s) = parse_header_line(&header_value, opts) {
+                    map.insert(&header_name, s);
                 }
             }
 
-            header_name = header.trim().to_ascii_lowercase();
+            header_name = header.trim().to_string();
             header_value = rest.trim_start().to_string();
         }
     }
 
-    if !header_value.is_empty() && wanted.contains(header_name.as_str()) {
-        if let Ok(s) = parse_header_line(&header_value) {
-            map.insert(header_name, s);
+    if !header_value.is_empty() && is_wanted(wanted, &header_name) {
+        if let Ok(s) = parse_header_line(&header_value, opts) {
+            map.insert(&header_name, s);
         }
     }
 
@@ -143,10 +245,44 @@ struct Args {
     #[arg(short, long)]
     list_colors: bool,
 
-    /// maildir directories
+    /// mbox quoting/boundary convention to assume when an input is a file
+    #[arg(long, value_enum, default_value = "auto")]
+    mbox_type: MboxType,
+
+    /// How to display the From header
+    #[arg(long, value_enum, default_value = "full")]
+    from_format: FromFormat,
+
+    /// Charset to assume for an encoded-word whose charset label isn't
+    /// recognized
+    #[arg(long, default_value = "windows-1252")]
+    fallback_charset: String,
+
+    /// Reject encoded-words with an unrecognized charset or malformed
+    /// byte sequence instead of lossily replacing bad bytes with U+FFFD
+    #[arg(long)]
+    strict_charset: bool,
+
+    /// Only show messages matching this query, e.g.
+    /// 'from:boss subject:/urgent|invoice/i'
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// maildir directories or mbox files
     inputs: Vec<String>,
 }
 
+/// How much of a `From` header to display.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FromFormat {
+    /// Display name only, falling back to the address if there is none.
+    Name,
+    /// Address only.
+    Address,
+    /// The original header value, unmodified.
+    Full,
+}
+
 use colored::Color;
 
 fn color_map() -> HashMap<&'static str, Color> {
@@ -189,16 +325,29 @@ fn list_colors() {
     }
 }
 
-fn format_header(
-    map: &HashMap<String, String>,
-    header: &str,
-    fallback: &str,
-    color: Color,
-) -> ColoredString {
-    map.get(header)
-        .map(|s| s.as_str())
-        .unwrap_or(fallback)
-        .color(color)
+fn format_header(map: &HeaderMap, header: &str, fallback: &str, color: Color) -> ColoredString {
+    map.get(header).unwrap_or(fallback).color(color)
+}
+
+/// Format the `From` header according to `format`, falling back to the
+/// raw header value if it doesn't parse as an RFC 5322 mailbox.
+fn format_from(map: &HeaderMap, format: FromFormat, fallback: &str, color: Color) -> ColoredString {
+    let raw = match map.get("from") {
+        Some(r) => r,
+        None => return fallback.color(color),
+    };
+
+    let text = match format {
+        FromFormat::Full => raw.to_string(),
+        FromFormat::Address => mailbox::parse_first_mailbox(raw)
+            .map(|mb| mb.address)
+            .unwrap_or_else(|| raw.to_string()),
+        FromFormat::Name => mailbox::parse_first_mailbox(raw)
+            .map(|mb| mb.display_name.filter(|s| !s.is_empty()).unwrap_or(mb.address))
+            .unwrap_or_else(|| raw.to_string()),
+    };
+
+    text.color(color)
 }
 
 fn main() {
@@ -216,28 +365,70 @@ fn main() {
     let subject_color = parse_color(&args.subject_color).unwrap_or(Color::BrightCyan);
     let from_color: Color = parse_color(&args.from_color).unwrap_or(Color::Cyan);
 
-    let mut paths = Vec::new();
+    let fallback_charset = match Encoding::for_label(args.fallback_charset.to_ascii_lowercase().as_bytes()) {
+        Some(encoding) => encoding,
+        None => {
+            eprintln!(
+                "Unknown fallback charset '{}', using windows-1252",
+                args.fallback_charset
+            );
+            encoding_rs::WINDOWS_1252
+        }
+    };
+
+    let decode_opts = DecodeOptions {
+        fallback_charset,
+        strict: args.strict_charset,
+    };
+
+    let filter = match &args.filter {
+        Some(q) => match query::parse(q) {
+            Ok(query) => Some(query),
+            Err(e) => {
+                eprintln!("Invalid filter: {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut maildir_paths = Vec::new();
+    let mut mbox_paths = Vec::new();
 
     for path in args.inputs {
-        let mut cur_path = PathBuf::from(&path);
+        let base = PathBuf::from(&path);
+
+        let mut cur_path = base.clone();
         cur_path.push("cur");
-        if cur_path.exists() {
-            paths.push(cur_path);
-        }
 
-        let mut new_path = PathBuf::from(&path);
+        let mut new_path = base.clone();
         new_path.push("new");
-        if new_path.exists() {
-            paths.push(new_path);
+
+        if cur_path.exists() || new_path.exists() {
+            if cur_path.exists() {
+                maildir_paths.push(cur_path);
+            }
+
+            if new_path.exists() {
+                maildir_paths.push(new_path);
+            }
+        } else if base.is_file() {
+            mbox_paths.push(base);
         }
     }
 
-    let wanted: HashSet<&str> = ["subject", "from"].into_iter().collect();
+    let mut wanted: HashSet<String> = ["subject", "from"].into_iter().map(String::from).collect();
+
+    if let Some(q) = &filter {
+        let mut names = Vec::new();
+        q.header_names(&mut names);
+        wanted.extend(names);
+    }
 
     let stdout = io::stdout();
     let mut out = io::BufWriter::new(stdout.lock());
 
-    for path in paths {
+    for path in maildir_paths {
         let basename = path
             .parent()
             .and_then(|p| p.file_name())
@@ -248,12 +439,18 @@ fn main() {
         let files = find_files(&path);
 
         for file in files {
-            let headers = read_headers(&file, &wanted);
+            let headers = read_headers(&file, &wanted, &decode_opts);
 
             match headers {
                 Ok(map) => {
+                    if let Some(q) = &filter {
+                        if !q.matches(&map) {
+                            continue;
+                        }
+                    }
+
                     let mailbox = basename.color(mailbox_color);
-                    let from = format_header(&map, "from", "no from", from_color);
+                    let from = format_from(&map, args.from_format, "no from", from_color);
                     let subject = format_header(&map, "subject", "no subject", subject_color);
 
                     if writeln!(out, "{}: {} / {}", mailbox, from, subject).is_err() {
@@ -268,4 +465,48 @@ fn main() {
             };
         }
     }
+
+    for path in mbox_paths {
+        let basename = path
+            .file_name()
+            .and_then(|p| p.to_str())
+            .unwrap_or_else(|| path.to_str().unwrap())
+            .to_string();
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => {
+                let _ = writeln!(out, "{}: <No subject> ({})", basename, e);
+                continue;
+            }
+        };
+
+        let mailbox = basename.color(mailbox_color);
+
+        for message in mbox::split_messages(&data, args.mbox_type) {
+            let headers = read_headers_from_bytes(&message, &wanted, &decode_opts);
+
+            match headers {
+                Ok(map) => {
+                    if let Some(q) = &filter {
+                        if !q.matches(&map) {
+                            continue;
+                        }
+                    }
+
+                    let from = format_from(&map, args.from_format, "no from", from_color);
+                    let subject = format_header(&map, "subject", "no subject", subject_color);
+
+                    if writeln!(out, "{}: {} / {}", mailbox, from, subject).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    if writeln!(out, "{}: <No subject> ({})", mailbox, e).is_err() {
+                        break;
+                    };
+                }
+            };
+        }
+    }
 }
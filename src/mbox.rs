@@ -0,0 +1,186 @@
+//! Reader for Unix mbox files, where a single file holds many messages
+//! separated by a `From ` delimiter line at column 0 (the postmark).
+//!
+//! Several incompatible conventions exist for how the body of a message is
+//! protected from accidentally containing a line that looks like a new
+//! delimiter. [`MboxType`] selects which convention to assume when
+//! splitting a file into individual messages.
+
+use clap::ValueEnum;
+use regex_macro::regex;
+
+/// Which mbox quoting/boundary convention to use when splitting a file into
+/// messages.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MboxType {
+    /// Split the whole file as mboxcl2 if every message in it has a usable
+    /// `Content-Length:`, otherwise split the whole file as mboxrd. No
+    /// message is skipped.
+    Auto,
+    /// Naively split on any `From ` delimiter line. No unescaping.
+    Mboxo,
+    /// Like mboxo, but body lines matching `^>+From ` are unescaped by
+    /// stripping one leading `>`.
+    Mboxrd,
+    /// Use a `Content-Length:` header to find the next message instead of
+    /// scanning for `From `. Body lines are unescaped like mboxrd.
+    Mboxcl,
+    /// Like mboxcl, but the body is never `>`-escaped.
+    Mboxcl2,
+}
+
+/// True if `line` is an mbox `From ` delimiter (a line beginning with
+/// `From ` followed by an address token and a date). The date is only
+/// checked for containing a digit.
+fn is_from_line(line: &[u8]) -> bool {
+    let Some(rest) = line.strip_prefix(b"From ") else {
+        return false;
+    };
+
+    let rest = String::from_utf8_lossy(rest);
+    let mut fields = rest.split_whitespace();
+
+    fields.next().is_some() && fields.any(|f| f.bytes().any(|b| b.is_ascii_digit()))
+}
+
+/// Unescape body lines that were quoted as `>From `, `>>From `, etc. by
+/// stripping exactly one leading `>` from any line matching `^>+From `.
+fn unescape_body(body: &[u8]) -> Vec<u8> {
+    let re = regex!(r"(?m)^(>+)From ");
+
+    let body_str = String::from_utf8_lossy(body);
+    let unescaped = re.replace_all(&body_str, |caps: &regex::Captures| {
+        format!("{}From ", &caps[1][1..])
+    });
+
+    unescaped.into_owned().into_bytes()
+}
+
+/// Split raw mbox bytes on `From ` delimiter lines. Returns each message's
+/// raw bytes (headers + body), unescaped if `unescape` is set.
+fn split_on_from_lines(data: &[u8], unescape: bool) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut line_start = 0usize;
+
+    for (i, _) in data.iter().enumerate() {
+        if i != line_start {
+            continue;
+        }
+
+        let line_end = data[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|p| line_start + p + 1)
+            .unwrap_or(data.len());
+
+        let line = &data[line_start..line_end];
+
+        if is_from_line(line) {
+            if let Some(s) = start {
+                messages.push(&data[s..line_start]);
+            }
+
+            start = Some(line_end);
+        }
+
+        line_start = line_end;
+
+        if line_start >= data.len() {
+            break;
+        }
+    }
+
+    if let Some(s) = start {
+        messages.push(&data[s..]);
+    }
+
+    messages
+        .into_iter()
+        .map(|m| if unescape { unescape_body(m) } else { m.to_vec() })
+        .collect()
+}
+
+/// Find the value of the `Content-Length` header in a raw message's header
+/// block, if present.
+fn content_length(message: &[u8]) -> Option<usize> {
+    let header_end = message
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .map(|p| p + 1)?;
+
+    let header_block = String::from_utf8_lossy(&message[..header_end]);
+
+    for line in header_block.lines() {
+        if let Some(rest) = line
+            .to_ascii_lowercase()
+            .strip_prefix("content-length:")
+            .map(|_| line.split_once(':').unwrap().1)
+        {
+            return rest.trim().parse::<usize>().ok();
+        }
+    }
+
+    None
+}
+
+/// Split raw mbox bytes using `Content-Length:` headers to locate message
+/// boundaries instead of scanning for `From ` lines.
+fn split_on_content_length(data: &[u8], unescape: bool) -> Option<Vec<Vec<u8>>> {
+    let mut messages = Vec::new();
+    let mut pos = 0usize;
+
+    while pos < data.len() {
+        let line_end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|p| pos + p + 1)?;
+
+        if !is_from_line(&data[pos..line_end]) {
+            return None;
+        }
+
+        let header_end = data[line_end..]
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map(|p| line_end + p + 2)?;
+
+        let len = content_length(&data[line_end..header_end])?;
+
+        let body_end = (header_end + len).min(data.len());
+        messages.push(data[line_end..body_end].to_vec());
+
+        pos = data[body_end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|p| body_end + p + 1)
+            .unwrap_or(data.len());
+    }
+
+    Some(if unescape {
+        messages.iter().map(|m| unescape_body(m)).collect()
+    } else {
+        messages
+    })
+}
+
+/// Split the raw contents of an mbox file into the raw bytes of each
+/// individual message, according to `mbox_type`.
+///
+/// In `Auto` mode, mboxcl2 is tried on the whole file first; if any message
+/// lacks a usable `Content-Length:`, the whole file is split as mboxrd
+/// instead. Individual malformed messages are not skipped.
+pub fn split_messages(data: &[u8], mbox_type: MboxType) -> Vec<Vec<u8>> {
+    match mbox_type {
+        MboxType::Mboxo => split_on_from_lines(data, false),
+        MboxType::Mboxrd => split_on_from_lines(data, true),
+        MboxType::Mboxcl => {
+            split_on_content_length(data, true).unwrap_or_else(|| split_on_from_lines(data, true))
+        }
+        MboxType::Mboxcl2 => {
+            split_on_content_length(data, false).unwrap_or_else(|| split_on_from_lines(data, false))
+        }
+        MboxType::Auto => split_on_content_length(data, false)
+            .unwrap_or_else(|| split_on_from_lines(data, true)),
+    }
+}
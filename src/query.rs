@@ -0,0 +1,146 @@
+//! A small query language for filtering messages by header, e.g.
+//! `from:boss subject:/urgent|invoice/i`.
+//!
+//! A query is a whitespace-separated list of `header:term` conditions.
+//! `term` is either a plain substring (matched case-insensitively
+//! against the decoded header value) or a `/regex/flags` literal
+//! compiled with the `regex` crate. Conditions combine with implicit AND;
+//! `or` combines everything to its left with the condition that follows
+//! it using OR instead, and `not` negates the condition that follows it.
+//! There is no precedence or grouping: terms combine strictly left to
+//! right, so `a b or c` means `(a AND b) OR c`.
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+
+use crate::headers::HeaderMap;
+
+enum Pattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+pub(crate) struct Condition {
+    header: String,
+    pattern: Pattern,
+}
+
+impl Condition {
+    fn matches(&self, map: &HeaderMap) -> bool {
+        let value = match map.get(&self.header) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        match &self.pattern {
+            Pattern::Substring(needle) => value.to_lowercase().contains(&needle.to_lowercase()),
+            Pattern::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+/// A parsed query, built from [`parse`].
+pub enum Query {
+    Cond(Condition),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    /// Evaluate the query against a message's headers.
+    pub fn matches(&self, map: &HeaderMap) -> bool {
+        match self {
+            Query::Cond(c) => c.matches(map),
+            Query::Not(q) => !q.matches(map),
+            Query::And(a, b) => a.matches(map) && b.matches(map),
+            Query::Or(a, b) => a.matches(map) || b.matches(map),
+        }
+    }
+
+    /// Collect the (lowercase) header names this query reads from, so the
+    /// caller knows which headers it needs to keep around.
+    pub fn header_names(&self, out: &mut Vec<String>) {
+        match self {
+            Query::Cond(c) => out.push(c.header.clone()),
+            Query::Not(q) => q.header_names(out),
+            Query::And(a, b) | Query::Or(a, b) => {
+                a.header_names(out);
+                b.header_names(out);
+            }
+        }
+    }
+}
+
+/// Parse a `/regex/flags` literal, or a plain substring if `term` doesn't
+/// look like one.
+fn parse_pattern(term: &str) -> Result<Pattern> {
+    if let Some(rest) = term.strip_prefix('/') {
+        if let Some(end) = rest.rfind('/') {
+            let body = &rest[..end];
+            let flags = &rest[end + 1..];
+
+            let pattern = if flags.contains('i') {
+                format!("(?i){}", body)
+            } else {
+                body.to_string()
+            };
+
+            let re = Regex::new(&pattern).map_err(|e| anyhow!("invalid regex /{}/: {}", body, e))?;
+            return Ok(Pattern::Regex(re));
+        }
+    }
+
+    Ok(Pattern::Substring(term.to_string()))
+}
+
+fn parse_condition(token: &str) -> Result<Condition> {
+    let (header, term) = token
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected header:term, got '{}'", token))?;
+
+    if header.is_empty() {
+        return Err(anyhow!("missing header name in '{}'", token));
+    }
+
+    Ok(Condition {
+        header: header.to_ascii_lowercase(),
+        pattern: parse_pattern(term)?,
+    })
+}
+
+fn parse_term(tokens: &[&str], pos: &mut usize) -> Result<Query> {
+    let token = *tokens
+        .get(*pos)
+        .ok_or_else(|| anyhow!("expected a condition, got end of query"))?;
+
+    if token.eq_ignore_ascii_case("not") {
+        *pos += 1;
+        let inner = parse_term(tokens, pos)?;
+        return Ok(Query::Not(Box::new(inner)));
+    }
+
+    *pos += 1;
+    Ok(Query::Cond(parse_condition(token)?))
+}
+
+/// Parse a filter query into an evaluable [`Query`].
+pub fn parse(input: &str) -> Result<Query> {
+    let tokens: Vec<&str> = input.split_whitespace().collect();
+    let mut pos = 0;
+
+    let mut node = parse_term(&tokens, &mut pos)?;
+
+    while pos < tokens.len() {
+        if tokens[pos].eq_ignore_ascii_case("or") {
+            pos += 1;
+            let rhs = parse_term(&tokens, &mut pos)?;
+            node = Query::Or(Box::new(node), Box::new(rhs));
+        } else {
+            let rhs = parse_term(&tokens, &mut pos)?;
+            node = Query::And(Box::new(node), Box::new(rhs));
+        }
+    }
+
+    Ok(node)
+}